@@ -0,0 +1,284 @@
+//! An async mirror of [`::io::Reader`](../io/struct.Reader.html), for verified decoding on top
+//! of a `tokio::io::AsyncRead + AsyncSeek` source. Gated behind the `async` feature.
+
+use std::cmp;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use decoder::Decoder;
+use seek_util::{add_offset, seek_from_end_before_header};
+
+// The poll-driven equivalent of the loop in io::Reader::read: ask the decoder what's needed,
+// seek and fill that many encoded bytes, feed them in, and emit whatever chunk bytes come back.
+// Because poll_* calls can return Pending at any point, each step of that loop needs to be a
+// state we can resume into on the next poll, rather than a loop we run straight through.
+//
+// `AsyncSeek` itself is two-phase (`start_seek` once, then `poll_complete` until it's ready), so
+// seeking the inner source gets its own state rather than folding into `Filling` the way a
+// blocking `Seek` call could.
+enum PollState {
+    // Nothing in flight; the next poll should ask the decoder what it needs.
+    Start,
+    // Seeking the inner source to `offset` before reading `len` bytes there. `started` tracks
+    // whether we've already called `start_seek`, since calling it twice for one seek is not
+    // allowed.
+    Seeking {
+        offset: u64,
+        len: usize,
+        started: bool,
+    },
+    // Reading the next `len` encoded bytes into `buf`, `filled` of which have arrived so far.
+    Filling {
+        len: usize,
+        buf: Vec<u8>,
+        filled: usize,
+    },
+    // `buf` is full; hand it to the decoder.
+    Feeding { buf: Vec<u8> },
+    // The decoder gave us a verified chunk; copy `chunk[start..]` out across as many poll_read
+    // calls as it takes to drain it.
+    Emitting { chunk: Vec<u8>, start: usize },
+}
+
+/// An adapter that implements `tokio::io::AsyncRead` and `tokio::io::AsyncSeek` on top of a
+/// `Decoder`, mirroring [`io::Reader`](../io/struct.Reader.html) but without blocking the
+/// executor while it drives the underlying source.
+pub struct AsyncReader<R> {
+    inner: R,
+    decoder: Decoder,
+    inner_position: u64,
+    position: u64,
+    state: PollState,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncReader<R> {
+    pub fn new(inner: R, header_hash: &::Digest) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(header_hash),
+            inner_position: 0,
+            position: 0,
+            state: PollState::Start,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncRead for AsyncReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        out: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            match mem::replace(&mut this.state, PollState::Start) {
+                PollState::Start => {
+                    let (offset, len) = this.decoder.needed();
+                    if len == 0 {
+                        // EOF. Leave the state as Start and report no bytes.
+                        return Poll::Ready(Ok(()));
+                    }
+                    this.state = if this.inner_position == offset {
+                        PollState::Filling {
+                            len,
+                            buf: vec![0; len],
+                            filled: 0,
+                        }
+                    } else {
+                        PollState::Seeking {
+                            offset,
+                            len,
+                            started: false,
+                        }
+                    };
+                }
+
+                PollState::Seeking {
+                    offset,
+                    len,
+                    started,
+                } => {
+                    if !started {
+                        match Pin::new(&mut this.inner).start_seek(io::SeekFrom::Start(offset)) {
+                            Ok(()) => {
+                                this.state = PollState::Seeking {
+                                    offset,
+                                    len,
+                                    started: true,
+                                };
+                            }
+                            Err(e) => return Poll::Ready(Err(e)),
+                        }
+                        continue;
+                    }
+                    match Pin::new(&mut this.inner).poll_complete(cx) {
+                        Poll::Ready(Ok(pos)) => {
+                            this.inner_position = pos;
+                            this.state = PollState::Filling {
+                                len,
+                                buf: vec![0; len],
+                                filled: 0,
+                            };
+                        }
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                        Poll::Pending => {
+                            this.state = PollState::Seeking {
+                                offset,
+                                len,
+                                started: true,
+                            };
+                            return Poll::Pending;
+                        }
+                    }
+                }
+
+                PollState::Filling {
+                    len,
+                    mut buf,
+                    mut filled,
+                } => {
+                    if filled < len {
+                        let mut read_buf = ReadBuf::new(&mut buf[filled..len]);
+                        match Pin::new(&mut this.inner).poll_read(cx, &mut read_buf) {
+                            Poll::Ready(Ok(())) => {
+                                let n = read_buf.filled().len();
+                                if n == 0 {
+                                    return Poll::Ready(Err(io::Error::new(
+                                        io::ErrorKind::UnexpectedEof,
+                                        "unexpected EOF while filling the decoder's next input",
+                                    )));
+                                }
+                                filled += n;
+                                this.inner_position += n as u64;
+                            }
+                            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                            Poll::Pending => {
+                                this.state = PollState::Filling { len, buf, filled };
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+
+                    this.state = if filled < len {
+                        PollState::Filling { len, buf, filled }
+                    } else {
+                        PollState::Feeding { buf }
+                    };
+                }
+
+                PollState::Feeding { buf } => {
+                    // A short underlying read can never make it here, since we always loop in
+                    // the Filling state until `buf` is completely full before feeding it.
+                    this.state = match this.decoder.feed(&buf) {
+                        Ok((_, Some(chunk))) => PollState::Emitting {
+                            chunk: chunk.to_vec(),
+                            start: 0,
+                        },
+                        Ok((_, None)) => PollState::Start,
+                        Err(::Error::ShortInput) => PollState::Start,
+                        Err(e) => {
+                            return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, e)))
+                        }
+                    };
+                }
+
+                PollState::Emitting { chunk, start } => {
+                    let n = cmp::min(out.remaining(), chunk.len() - start);
+                    out.put_slice(&chunk[start..start + n]);
+                    this.position += n as u64;
+                    this.state = if start + n < chunk.len() {
+                        PollState::Emitting {
+                            chunk,
+                            start: start + n,
+                        }
+                    } else {
+                        PollState::Start
+                    };
+                    return Poll::Ready(Ok(()));
+                }
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncSeek for AsyncReader<R> {
+    fn start_seek(self: Pin<&mut Self>, position: io::SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        let new_position = match position {
+            io::SeekFrom::Start(p) => p,
+            io::SeekFrom::Current(delta) => add_offset(this.position, delta)?,
+            io::SeekFrom::End(delta) => {
+                let len = this
+                    .decoder
+                    .len()
+                    .ok_or_else(seek_from_end_before_header)?;
+                add_offset(len, delta)?
+            }
+        };
+        this.decoder.seek(new_position);
+        this.position = new_position;
+        // Whatever we'd buffered in PollState no longer corresponds to the new position.
+        this.state = PollState::Start;
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(self.get_mut().position))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    use super::*;
+
+    // `std::io::Cursor` already implements both `AsyncRead` and `AsyncSeek` (poll-completing
+    // immediately, never Pending), which is enough to drive the whole Seeking/Filling/Feeding/
+    // Emitting state machine without a hand-rolled mock.
+    #[tokio::test]
+    async fn test_async_reader() {
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> starting case {}", case);
+            let input = vec![0x72; case];
+            let (encoded, hash) = ::simple::encode(&input);
+            let mut reader = AsyncReader::new(Cursor::new(encoded), &hash);
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).await.unwrap();
+            assert_eq!(input, output);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_seek() {
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> case {}", case);
+            let input: Vec<u8> = (0..case).map(|i| i as u8).collect();
+            let (encoded, hash) = ::simple::encode(&input);
+            for &seek_case in ::TEST_CASES {
+                if seek_case > case {
+                    continue;
+                }
+                println!(">>> seek case {}", seek_case);
+                let mut reader = AsyncReader::new(Cursor::new(encoded.clone()), &hash);
+                reader
+                    .seek(io::SeekFrom::Start(seek_case as u64))
+                    .await
+                    .unwrap();
+                let mut output = Vec::new();
+                reader.read_to_end(&mut output).await.unwrap();
+                assert_eq!(&input[seek_case..], &output[..]);
+            }
+        }
+    }
+}
@@ -1,3 +1,11 @@
+// The decoder's only heap allocation is `stack: Vec<Node>`, and `Unverified`/`State` are
+// otherwise allocation-free, so nothing here is inherently tied to `std`. But this tree has no
+// Cargo.toml or lib.rs to define a `std` feature and wire up `#![no_std] + extern crate alloc`,
+// so there is nowhere for a `#[cfg(not(feature = "std"))]` gate to get its cfg value from, and
+// gating the test module on that feature would just disable it unconditionally. Until that
+// scaffold lands, this module stays std-only and its tests run under plain `#[cfg(test)]`, same
+// as the rest of the crate.
+
 use node::{Region, Node};
 use unverified::Unverified;
 
@@ -0,0 +1,176 @@
+use std::cmp;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+use decoder::Decoder;
+use seek_util::{add_offset, seek_from_end_before_header};
+
+/// An adapter that implements `std::io::Read` and `std::io::Seek` on top of a `Decoder`, given
+/// an underlying encoded source that is itself `Read + Seek`.
+///
+/// Every `read` call drives the decoder by hand: it asks `needed()` for the next
+/// `(encoded_offset, len)`, seeks the inner reader there if it isn't already positioned
+/// correctly, reads exactly that many bytes into a scratch buffer, and feeds them in. Any chunk
+/// bytes that come back are buffered internally and handed out across however many `read` calls
+/// it takes to drain them. This makes a bao encoding usable as an ordinary verified byte stream.
+#[derive(Clone, Debug)]
+pub struct Reader<R> {
+    inner: R,
+    decoder: Decoder,
+    inner_position: u64,
+    position: u64,
+    buf: Vec<u8>,
+    buf_start: usize,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    pub fn new(inner: R, header_hash: &::Digest) -> Self {
+        Self {
+            inner,
+            decoder: Decoder::new(header_hash),
+            inner_position: 0,
+            position: 0,
+            buf: Vec::new(),
+            buf_start: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn buffered_len(&self) -> usize {
+        self.buf.len() - self.buf_start
+    }
+}
+
+impl<R: Read + Seek> Read for Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.buffered_len() > 0 {
+                let n = cmp::min(buf.len(), self.buffered_len());
+                buf[..n].copy_from_slice(&self.buf[self.buf_start..self.buf_start + n]);
+                self.buf_start += n;
+                self.position += n as u64;
+                return Ok(n);
+            }
+
+            let (offset, len) = self.decoder.needed();
+            if len == 0 {
+                return Ok(0);
+            }
+
+            if self.inner_position != offset {
+                self.inner.seek(SeekFrom::Start(offset))?;
+                self.inner_position = offset;
+            }
+            let mut scratch = vec![0; len];
+            match self.inner.read_exact(&mut scratch) {
+                Ok(()) => {}
+                Err(e) => return Err(e),
+            }
+            self.inner_position += len as u64;
+
+            match self.decoder.feed(&scratch) {
+                Ok((_, Some(chunk))) => {
+                    self.buf.clear();
+                    self.buf.extend_from_slice(chunk);
+                    self.buf_start = 0;
+                }
+                Ok((_, None)) => {
+                    // A header or node was consumed; loop back around and ask what's needed
+                    // next.
+                }
+                Err(::Error::ShortInput) => {
+                    // We handed over exactly what needed() asked for, so this shouldn't really
+                    // happen, but if it does it just means we need to go read more.
+                }
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Seek for Reader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_position = match pos {
+            SeekFrom::Start(p) => p,
+            SeekFrom::Current(delta) => add_offset(self.position, delta)?,
+            SeekFrom::End(delta) => {
+                let len = self
+                    .decoder
+                    .len()
+                    .ok_or_else(seek_from_end_before_header)?;
+                add_offset(len, delta)?
+            }
+        };
+        self.decoder.seek(new_position);
+        self.position = new_position;
+        // The decoder's internal state no longer corresponds to whatever chunk we'd buffered.
+        self.buf.clear();
+        self.buf_start = 0;
+        Ok(new_position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::io::{Read, Seek, SeekFrom};
+
+    use super::*;
+
+    #[test]
+    fn test_reader() {
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> starting case {}", case);
+            let input = vec![0x72; case];
+            let (encoded, hash) = ::simple::encode(&input);
+            let mut reader = Reader::new(Cursor::new(&encoded), &hash);
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).unwrap();
+            assert_eq!(input, output);
+        }
+    }
+
+    #[test]
+    fn test_reader_small_reads() {
+        // The read sizes read_to_end above uses are chosen by std, so it can't be relied on to
+        // exercise buf_start advancing partway through a chunk. Force that by reading a single
+        // byte at a time across a multi-chunk file.
+        let case = 4 * ::CHUNK_SIZE + 1;
+        let input: Vec<u8> = (0..case).map(|i| i as u8).collect();
+        let (encoded, hash) = ::simple::encode(&input);
+        let mut reader = Reader::new(Cursor::new(&encoded), &hash);
+        let mut output = Vec::new();
+        let mut byte = [0; 1];
+        loop {
+            match reader.read(&mut byte) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&byte[..n]),
+                Err(e) => panic!("unexpected error: {:?}", e),
+            }
+        }
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    fn test_reader_seek() {
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> case {}", case);
+            let input: Vec<u8> = (0..case).map(|i| i as u8).collect();
+            let (encoded, hash) = ::simple::encode(&input);
+            for &seek_case in ::TEST_CASES {
+                if seek_case > case {
+                    continue;
+                }
+                println!(">>> seek case {}", seek_case);
+                let mut reader = Reader::new(Cursor::new(&encoded), &hash);
+                reader.seek(SeekFrom::Start(seek_case as u64)).unwrap();
+                let mut output = Vec::new();
+                reader.read_to_end(&mut output).unwrap();
+                assert_eq!(&input[seek_case..], &output[..]);
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// Shared by the sync and async `Reader` adapters (`io.rs`, `async_io.rs`), which both need to
+// turn a `SeekFrom` into an absolute position the same way.
+use std::io;
+
+/// Add a signed seek delta to a base position, for `SeekFrom::Current`/`SeekFrom::End` handling.
+pub fn add_offset(base: u64, offset: i64) -> io::Result<u64> {
+    let result = if offset < 0 {
+        base.checked_sub((-offset) as u64)
+    } else {
+        base.checked_add(offset as u64)
+    };
+    result.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+/// The error for a `SeekFrom::End` before the header (and so the content length) has been read.
+pub fn seek_from_end_before_header() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "seek from end before the header has been read",
+    )
+}
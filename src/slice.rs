@@ -0,0 +1,295 @@
+// Like decoder.rs, this only ever needs `Vec`, so nothing here is inherently tied to `std`; see
+// the comment there about why it stays std-only (and its tests plain `#[cfg(test)]`) until this
+// tree has a Cargo.toml/lib.rs to define a `std` feature for a `#[cfg(not(feature = "std"))]`
+// gate to key off of.
+
+use node::Region;
+use unverified::Unverified;
+
+/// Extracts a minimal, self-verifying slice of a bao encoding covering the content range
+/// `[start, start + len)`, for a peer to verify with `SliceDecoder` against the same root hash,
+/// without needing the whole encoding — handy for range requests over a network transport.
+///
+/// `encoded` must already be a complete, valid encoding; this function doesn't re-verify it,
+/// since the whole point is to shave hashes for a peer who doesn't have them yet. Every node and
+/// chunk whose region overlaps the requested range is included verbatim, and subtrees entirely
+/// outside the range are skipped; no sibling hashes need to be attached, because each node's
+/// serialized bytes already carry both of its children's hashes.
+pub fn extract_slice(encoded: &[u8], start: u64, len: u64) -> Vec<u8> {
+    let header_bytes = &encoded[..::HEADER_SIZE];
+    let header_array = array_ref!(header_bytes, 0, ::HEADER_SIZE);
+    let header = Region::from_header_bytes(header_array);
+    // Clamp to the actual content length, same as a normal seek past EOF would.
+    let end = start.saturating_add(len).min(header.end);
+
+    let mut output = Vec::new();
+    output.extend_from_slice(header_bytes);
+    // The root is always included, even for an empty range: there's no way to check anything
+    // against the header hash without it.
+    extract_region(encoded, header, start, end, &mut output);
+    output
+}
+
+fn extract_region(encoded: &[u8], region: Region, start: u64, end: u64, output: &mut Vec<u8>) {
+    let offset = region.encoded_offset as usize;
+    if region.len() <= ::CHUNK_SIZE as u64 {
+        output.extend_from_slice(&encoded[offset..offset + region.len() as usize]);
+        return;
+    }
+    let node_bytes = &encoded[offset..offset + ::NODE_SIZE];
+    output.extend_from_slice(node_bytes);
+    let node = region
+        .parse_node(node_bytes)
+        .expect("extract_slice requires a complete, valid encoding");
+    if overlaps(node.left, start, end) {
+        extract_region(encoded, node.left, start, end, output);
+    }
+    if overlaps(node.right, start, end) {
+        extract_region(encoded, node.right, start, end, output);
+    }
+}
+
+// Half-open interval overlap. Note that a range touching a region's edge exactly (end ==
+// region.start, or region.end == start) does not overlap, so a boundary-aligned request never
+// pulls in the neighboring subtree.
+fn overlaps(region: Region, start: u64, end: u64) -> bool {
+    region.start < end && start < region.end
+}
+
+/// A restricted `Decoder` that verifies only a `[start, start + len)` slice produced by
+/// `extract_slice`, against the same root hash the full encoding would verify against.
+///
+/// Like `Decoder`, this is a pull-based state machine: call `needed()` for the next `(offset,
+/// len)` to feed, and `feed()` once you have those bytes. The offsets `needed()` returns are
+/// positions within the *slice*, not within the original encoding, since out-of-range subtrees
+/// were never included in it. A slice that's missing a node on the path into the requested range
+/// will fail verification the same way any other tampered input would: either `feed()` returns
+/// `Err`, or the bytes run out before `needed()` reports EOF.
+///
+/// Traversal mirrors `extract_region` exactly, rather than stepping through by position the way
+/// `Decoder` does: `pending` holds the regions still to be fed, in the same preorder that
+/// `extract_region` wrote them in, and feeding a node pushes only the children `overlaps` the
+/// requested range, same as `extract_region` only recursing into those. That symmetry matters at
+/// the edges: a zero-length range that lands exactly on a split point overlaps neither child, so
+/// both sides stop descending at the same node without either one needing to track "have we seen
+/// the root yet" as a special case.
+#[derive(Debug, Clone)]
+pub struct SliceDecoder {
+    header_hash: ::Digest,
+    header: Option<Region>,
+    start: u64,
+    end: u64,
+    slice_offset: u64,
+    pending: Vec<Region>,
+}
+
+impl SliceDecoder {
+    pub fn new(header_hash: &::Digest, start: u64, len: u64) -> Self {
+        Self {
+            header_hash: *header_hash,
+            header: None,
+            start,
+            end: start.saturating_add(len),
+            slice_offset: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> Option<u64> {
+        self.header.map(|h| h.len())
+    }
+
+    // Give the (slice_offset, size) needed in the next call to feed(). A size of zero means
+    // we've reached the end of the requested range.
+    pub fn needed(&self) -> (u64, usize) {
+        if self.header.is_none() {
+            return (self.slice_offset, ::HEADER_SIZE);
+        }
+        match self.pending.last() {
+            None => (self.slice_offset, 0),
+            Some(region) if region.len() <= ::CHUNK_SIZE as u64 => {
+                (self.slice_offset, region.len() as usize)
+            }
+            Some(_) => (self.slice_offset, ::NODE_SIZE),
+        }
+    }
+
+    pub fn feed<'a>(&mut self, input: &'a [u8]) -> ::Result<(usize, Option<&'a [u8]>)> {
+        let mut input = Unverified::wrap(input);
+        if self.header.is_none() {
+            return self.feed_header(&mut input);
+        }
+        match self.pending.pop() {
+            None => Ok((0, None)),
+            Some(region) => {
+                if region.len() <= ::CHUNK_SIZE as u64 {
+                    self.feed_chunk(&mut input, region)
+                } else {
+                    self.feed_node(&mut input, region)
+                }
+            }
+        }
+    }
+
+    fn feed_header<'a>(
+        &mut self,
+        input: &mut Unverified<'a>,
+    ) -> ::Result<(usize, Option<&'a [u8]>)> {
+        let header_bytes = input.read_verify(::HEADER_SIZE, &self.header_hash)?;
+        let header_array = array_ref!(header_bytes, 0, ::HEADER_SIZE);
+        let header = Region::from_header_bytes(header_array);
+        // Clamp to the actual content length, same as extract_slice does.
+        self.end = self.end.min(header.end);
+        self.header = Some(header);
+        // The root region is always fed, even for an empty range: there's no way to check
+        // anything against the header hash without it. This mirrors extract_slice always
+        // recursing into the root region unconditionally.
+        self.pending.push(header);
+        self.slice_offset += ::HEADER_SIZE as u64;
+        Ok((::HEADER_SIZE, None))
+    }
+
+    fn feed_chunk<'a>(
+        &mut self,
+        input: &mut Unverified<'a>,
+        region: Region,
+    ) -> ::Result<(usize, Option<&'a [u8]>)> {
+        let chunk_bytes = input.read_verify(region.len() as usize, &region.hash)?;
+        // Trim to the overlap between this chunk and the requested range (the whole chunk still
+        // has to be hashed; only the output is clipped). For a chunk that doesn't actually
+        // overlap — the root chunk when the range is empty or starts at or past the end of the
+        // content, or the chunk an empty range lands strictly inside of — this comes out to an
+        // empty slice rather than needing a separate case.
+        let clipped_start = region.start.max(self.start).min(region.end);
+        let clipped_end = region.end.min(self.end).max(clipped_start);
+        let start_offset = (clipped_start - region.start) as usize;
+        let end_offset = (clipped_end - region.start) as usize;
+        let ret = &chunk_bytes[start_offset..end_offset];
+        self.slice_offset += chunk_bytes.len() as u64;
+        Ok((chunk_bytes.len(), Some(ret)))
+    }
+
+    fn feed_node<'a>(
+        &mut self,
+        input: &mut Unverified<'a>,
+        region: Region,
+    ) -> ::Result<(usize, Option<&'a [u8]>)> {
+        let node_bytes = input.read_verify(::NODE_SIZE, &region.hash)?;
+        let node = region.parse_node(node_bytes)?;
+        // Push right before left, so left pops (and is fed) first — preorder, matching
+        // extract_region's recursion order. A child that doesn't overlap the requested range is
+        // never pushed, matching extract_region skipping it entirely.
+        if overlaps(node.right, self.start, self.end) {
+            self.pending.push(node.right);
+        }
+        if overlaps(node.left, self.start, self.end) {
+            self.pending.push(node.left);
+        }
+        self.slice_offset += ::NODE_SIZE as u64;
+        Ok((::NODE_SIZE, None))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Drives a SliceDecoder over a slice extracted for `[start, start + len)`, checking that the
+    // whole slice gets consumed (no leftover bytes the decoder never asked for) by the time it
+    // reports EOF, and returns the verified output.
+    fn decode_slice(encoded: &[u8], hash: &::Digest, start: u64, len: u64) -> Vec<u8> {
+        let slice = extract_slice(encoded, start, len);
+        let mut decoder = SliceDecoder::new(hash, start, len);
+        let mut output = Vec::new();
+        let mut remaining = &slice[..];
+        loop {
+            let (offset, needed_len) = decoder.needed();
+            if needed_len == 0 {
+                break;
+            }
+            assert_eq!(offset as usize, slice.len() - remaining.len());
+            let feed_slice = &remaining[..needed_len];
+            let (consumed, maybe_output) = decoder.feed(feed_slice).unwrap();
+            assert_eq!(consumed, needed_len);
+            if let Some(bytes) = maybe_output {
+                output.extend_from_slice(bytes);
+            }
+            remaining = &remaining[consumed..];
+        }
+        assert!(
+            remaining.is_empty(),
+            "slice had {} leftover bytes the decoder never asked for",
+            remaining.len()
+        );
+        output
+    }
+
+    #[test]
+    fn test_slice_round_trip() {
+        // Ranges that extend all the way to EOF.
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> starting case {}", case);
+            let input = vec![0x72; case];
+            let (encoded, hash) = ::simple::encode(&input);
+            for &slice_start in ::TEST_CASES {
+                if slice_start > case {
+                    continue;
+                }
+                println!(">>> slice start {}", slice_start);
+                let remaining_len = (case - slice_start) as u64;
+                let output = decode_slice(&encoded, &hash, slice_start as u64, remaining_len);
+                assert_eq!(&input[slice_start..], &output[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_partial_range() {
+        // Ranges of several lengths from each start, most of them ending strictly inside the file
+        // (including some that land mid-chunk) and one running to EOF, to
+        // exercise the tail-clamping that `SliceDecoder::feed_chunk` does on top of the
+        // existing seek-offset clamping at the front of a chunk.
+        for &case in ::TEST_CASES {
+            if case == 0 {
+                continue;
+            }
+            println!("\n>>>>> starting case {}", case);
+            let input: Vec<u8> = (0..case).map(|i| i as u8).collect();
+            let (encoded, hash) = ::simple::encode(&input);
+            for &slice_start in ::TEST_CASES {
+                if slice_start >= case {
+                    continue;
+                }
+                let remaining = case - slice_start;
+                for &len in &[1, remaining / 3 + 1, remaining / 2 + 1, remaining] {
+                    if len == 0 || len > remaining {
+                        continue;
+                    }
+                    println!(">>> slice start {} len {}", slice_start, len);
+                    let output = decode_slice(&encoded, &hash, slice_start as u64, len as u64);
+                    let end = slice_start + len;
+                    assert_eq!(&input[slice_start..end], &output[..]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_empty_range() {
+        // An empty range still has to verify the root region against the header hash, even
+        // though there's no output to produce.
+        for &case in ::TEST_CASES {
+            println!("\n>>>>> starting case {}", case);
+            let input = vec![0x72; case];
+            let (encoded, hash) = ::simple::encode(&input);
+            for &start in ::TEST_CASES {
+                if start > case {
+                    continue;
+                }
+                println!(">>> start {}", start);
+                let output = decode_slice(&encoded, &hash, start as u64, 0);
+                assert!(output.is_empty());
+            }
+        }
+    }
+}